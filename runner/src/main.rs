@@ -1,19 +1,34 @@
 #[macro_use]
 extern crate failure;
 extern crate json;
+extern crate regex;
 extern crate wait_timeout;
 
 mod runner;
 
 use std::process::{self, Command, Stdio};
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fs::{File, remove_file};
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use std::str::from_utf8;
-use runner::{CoverageRunner, FullSuiteRunner, Runner, Status};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use regex::Regex;
+use runner::{CoverageRunner, DoctestRunner, FullSuiteRunner, Runner, Status};
 
 static TARGET_MUTAGEN: &'static str = "target/mutagen";
 static MUTATIONS_LIST: &'static str = "mutations.txt";
+static LOOPS_FILE: &'static str = "target/mutagen/loops.txt";
+
+/// Default multiplier applied to the baseline run's duration to get a
+/// per-mutation timeout; overridable with `--timeout-factor`.
+const DEFAULT_TIMEOUT_FACTOR: f64 = 3.0;
+/// However fast the baseline ran, never give a mutation less than this,
+/// so a near-instant baseline doesn't produce a degenerate zero timeout.
+const TIMEOUT_FLOOR: Duration = Duration::from_secs(1);
 
 type Result<T> = std::result::Result<T, failure::Error>;
 
@@ -45,40 +60,475 @@ impl<'a> Mutation<'a> {
     }
 }
 
-fn run_mutations(runner: &mut Runner, list: &[String]) -> Result<()> {
-    let max_mutation = list.len();
-    let mut failures = 0usize;
+/// Builds a fresh `Runner` of the same kind the caller asked for. Each
+/// worker thread gets its own instance so runners never share mutable state.
+fn new_runner(test_executable: &PathBuf, with_coverage: bool) -> Box<Runner> {
+    if with_coverage {
+        Box::new(CoverageRunner::new(test_executable.clone()))
+    } else {
+        Box::new(FullSuiteRunner::new(test_executable.clone()))
+    }
+}
+
+/// How to report mutation outcomes once a run has finished.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// the classic human-readable prose
+    Text,
+    /// one JSON object per mutation plus a summary, for CI to parse
+    Json,
+    /// text output plus `::warning` annotations GitHub Actions renders inline on PRs
+    Github,
+}
+
+fn output_format() -> Result<OutputFormat> {
+    match flag_value("--format").as_deref() {
+        None => Ok(OutputFormat::Text),
+        Some("json") => Ok(OutputFormat::Json),
+        Some("github") => Ok(OutputFormat::Github),
+        Some(other) => bail!("unknown --format {:?}, expected \"json\" or \"github\"", other),
+    }
+}
+
+/// Splits a span like `src/lib.rs:27:21: 27:22` into `(file, line, col)` of
+/// where the mutation starts.
+fn parse_span(span: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = span.splitn(3, ':');
+    let file = parts.next()?;
+    let line = parts.next()?;
+    let col = parts.next()?.split(':').next()?.trim();
+    Some((file, line, col))
+}
+
+/// Which mutations `run_mutations` should actually run, built from
+/// `--filter`, `--exclude` and `--file`.
+struct Filters {
+    filter: Option<Regex>,
+    exclude: Option<Regex>,
+    file: Option<Regex>,
+}
 
-    println!("Running {} mutations\n", max_mutation);
-    for m in list {
-        // Mutation count starts from 1 (0 is not mutations)
-        let mutation = Mutation::from(m)?;
+impl Filters {
+    fn from_args() -> Result<Filters> {
+        Ok(Filters {
+            filter: flag_value("--filter").as_deref().map(Regex::new).transpose()?,
+            exclude: flag_value("--exclude").as_deref().map(Regex::new).transpose()?,
+            file: flag_value("--file").as_deref().map(glob_to_regex).transpose()?,
+        })
+    }
 
-        print!("{} {} ({})", mutation.description, mutation.span, mutation.count);
+    /// Whether `mutation` should be kept given these filters.
+    fn keeps(&self, mutation: &Mutation) -> bool {
+        let text = format!("{} @ {}", mutation.description, mutation.span);
+        if let Some(ref filter) = self.filter {
+            if !filter.is_match(&text) {
+                return false;
+            }
+        }
+        if let Some(ref exclude) = self.exclude {
+            if exclude.is_match(&text) {
+                return false;
+            }
+        }
+        if let Some(ref file) = self.file {
+            let path = mutation.span.split(':').next().unwrap_or("");
+            if !file.is_match(path) {
+                return false;
+            }
+        }
+        true
+    }
+}
 
-        let result = runner.run(mutation.count)?;
+/// Turns a simple `*`-wildcard glob (e.g. `src/foo/*.rs`) into an anchored regex.
+fn glob_to_regex(glob: &str) -> Result<Regex> {
+    let escaped: Vec<String> = glob.split('*').map(regex::escape).collect();
+    Ok(Regex::new(&format!("^{}$", escaped.join(".*")))?)
+}
 
-        let status = if let Status::Success = result {
-            // A succeeding test suite is actually a failure for us.
-            // At least on test should have failed
-            failures += 1;
+fn text_label(status: Status) -> &'static str {
+    match status {
+        Status::Success => "SURVIVED :(",
+        Status::Failure => "caught",
+        Status::Timeout => "caught (TIMEOUT)",
+    }
+}
 
-            // change the output message to avoid the success<->failure inversion confusion. --bblum
-            "SURVIVED :("
+fn print_text_report(
+    results: &[(&Mutation, Status)],
+    failures: usize,
+    total: usize,
+    skipped: usize,
+    caught_by_doctest: &HashSet<usize>,
+) {
+    for (mutation, status) in results {
+        let suffix = if caught_by_doctest.contains(&mutation.count) {
+            " [doctest]"
         } else {
-            // "killed" in the google paper but let's avoid violent language
-            "caught"
+            ""
         };
-
-        println!(" ... {}", status);
+        println!(
+            "{} {} ({}) ... {}{}",
+            mutation.description, mutation.span, mutation.count, text_label(*status), suffix
+        );
     }
 
     println!(
-        "\nMutation results: {}. {} caught by existing tests; {} were undetected\n",
+        "\nMutation results: {}. {} caught by existing tests ({} by doctests); {} were undetected; {} skipped\n",
         if failures == 0 { "ok" } else { "FAILED" },
-        list.len() - failures,
-        failures
+        total - failures,
+        caught_by_doctest.len(),
+        failures,
+        skipped
     );
+}
+
+fn print_github_annotations(results: &[(&Mutation, Status)]) {
+    for (mutation, status) in results {
+        if let Status::Success = status {
+            match parse_span(mutation.span) {
+                Some((file, line, col)) => println!(
+                    "::warning file={},line={},col={}::mutation survived: {}",
+                    file, line, col, mutation.description
+                ),
+                None => eprintln!("could not parse span {:?} for annotation", mutation.span),
+            }
+        }
+    }
+}
+
+fn print_json_report(results: &[(&Mutation, Status)], skipped: usize, caught_by_doctest: &HashSet<usize>) {
+    let mut mutations = json::JsonValue::new_array();
+    let mut survived = 0usize;
+    for (mutation, status) in results {
+        let status_str = match status {
+            Status::Success => {
+                survived += 1;
+                "survived"
+            }
+            Status::Failure => "caught",
+            Status::Timeout => "timeout",
+        };
+        let mut record = json::JsonValue::new_object();
+        record["count"] = mutation.count.into();
+        record["description"] = mutation.description.into();
+        record["span"] = mutation.span.into();
+        record["status"] = status_str.into();
+        record["caught_by"] = if caught_by_doctest.contains(&mutation.count) {
+            "doctest".into()
+        } else if status_str == "survived" {
+            json::JsonValue::Null
+        } else {
+            "unit".into()
+        };
+        mutations.push(record).expect("mutations is always an array");
+    }
+
+    let mut summary = json::JsonValue::new_object();
+    summary["total"] = (results.len() + skipped).into();
+    summary["caught"] = (results.len() - survived).into();
+    summary["caught_by_doctest"] = caught_by_doctest.len().into();
+    summary["survived"] = survived.into();
+    summary["skipped"] = skipped.into();
+
+    let mut report = json::JsonValue::new_object();
+    report["mutations"] = mutations;
+    report["summary"] = summary;
+    println!("{}", report.dump());
+}
+
+/// Stable identity for a mutation across runs: the numeric `count` shifts
+/// as code changes, but the description+span pair doesn't.
+fn format_key(description: &str, span: &str) -> String {
+    format!("{} @ {}", description, span)
+}
+
+fn mutation_key(mutation: &Mutation) -> String {
+    format_key(mutation.description, mutation.span)
+}
+
+/// A surviving mutation as recorded in a `--baseline` snapshot file.
+struct BaselineEntry {
+    description: String,
+    span: String,
+}
+
+impl BaselineEntry {
+    fn key(&self) -> String {
+        format_key(&self.description, &self.span)
+    }
+}
+
+fn read_baseline(path: &Path) -> Result<Vec<BaselineEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut file = File::open(path)?;
+    let mut s = String::new();
+    file.read_to_string(&mut s)?;
+    let survivors = json::parse(&s)?;
+    Ok(survivors
+        .members()
+        .map(|entry| BaselineEntry {
+            description: entry["description"].as_str().unwrap_or("").to_string(),
+            span: entry["span"].as_str().unwrap_or("").to_string(),
+        })
+        .collect())
+}
+
+/// Writes the snapshot: this run's survivors plus `carried_over` entries
+/// for mutations the run never considered (e.g. filtered out), so they
+/// aren't lost from the file just because this invocation didn't look at
+/// them.
+fn write_baseline(path: &Path, results: &[(&Mutation, Status)], carried_over: &[BaselineEntry]) -> Result<()> {
+    let mut survivors = json::JsonValue::new_array();
+    for (mutation, status) in results {
+        if let Status::Success = status {
+            let mut entry = json::JsonValue::new_object();
+            entry["description"] = mutation.description.into();
+            entry["span"] = mutation.span.into();
+            survivors.push(entry).expect("survivors is always an array");
+        }
+    }
+    for entry in carried_over {
+        let mut obj = json::JsonValue::new_object();
+        obj["description"] = entry.description.clone().into();
+        obj["span"] = entry.span.clone().into();
+        survivors.push(obj).expect("survivors is always an array");
+    }
+    let mut file = File::create(path)?;
+    file.write_all(survivors.pretty(2).as_bytes())?;
+    Ok(())
+}
+
+/// Compares this run's surviving mutations against the snapshot at `path`,
+/// prints what newly survived/was newly caught since then, rewrites the
+/// snapshot to the current state, and reports whether a regression (a
+/// surviving mutation the snapshot doesn't list) was found. The snapshot
+/// records survivors only, so a mutation added since it was written that
+/// survives counts as a regression just like a previously caught one. When
+/// no snapshot exists yet, nothing counts as a regression and this run's
+/// survivors become the snapshot.
+///
+/// `results` only covers mutations that survived this run's `--filter`/
+/// `--exclude`/`--file`; `all_keys` is every mutation in the codebase this
+/// invocation saw regardless of filtering, so a baseline entry outside
+/// `results` can be told apart as either filtered-out-but-still-real
+/// (carried over untouched) or genuinely stale (dropped).
+fn check_baseline(path: &Path, results: &[(&Mutation, Status)], all_keys: &HashSet<String>) -> Result<bool> {
+    let had_baseline = path.exists();
+    let previous = read_baseline(path)?;
+    let previous_survivors: HashSet<String> = previous.iter().map(BaselineEntry::key).collect();
+    let current_keys: HashSet<String> = results.iter().map(|(m, _)| mutation_key(m)).collect();
+    let current_survivors: HashSet<String> = results
+        .iter()
+        .filter(|(_, status)| *status == Status::Success)
+        .map(|(m, _)| mutation_key(m))
+        .collect();
+
+    // Baseline entries for a mutation this run didn't execute -- whether
+    // filtered out or genuinely gone -- are neither newly caught nor newly
+    // surviving; see the `carried_over`/stale handling below instead.
+    let newly_caught: Vec<&String> = previous_survivors
+        .iter()
+        .filter(|key| current_keys.contains(*key) && !current_survivors.contains(*key))
+        .collect();
+    let newly_surviving: Vec<&String> = if had_baseline {
+        current_survivors.difference(&previous_survivors).collect()
+    } else {
+        Vec::new()
+    };
+
+    if !newly_caught.is_empty() {
+        println!("\nNewly caught since baseline:");
+        for key in &newly_caught {
+            println!("  + {}", key);
+        }
+    }
+    if !newly_surviving.is_empty() {
+        println!("\nNewly surviving since baseline (regression):");
+        for key in &newly_surviving {
+            println!("  - {}", key);
+        }
+    }
+
+    // Mutations this run never ran but which still exist (filtered out)
+    // are neither confirmed caught nor confirmed surviving -- keep their
+    // prior entry as-is. Entries for spans missing from `all_keys` too are
+    // genuinely stale (the code moved on) and get dropped instead.
+    let carried_over: Vec<BaselineEntry> = previous
+        .into_iter()
+        .filter(|entry| {
+            let key = entry.key();
+            !current_keys.contains(&key) && all_keys.contains(&key)
+        })
+        .collect();
+
+    write_baseline(path, results, &carried_over)?;
+    Ok(!newly_surviving.is_empty())
+}
+
+/// Everything about how to run a batch of mutations that doesn't vary
+/// per-mutation: parsed once in `run()` from the command line.
+struct RunOptions<'a> {
+    jobs: usize,
+    format: OutputFormat,
+    filters: &'a Filters,
+    baseline: Option<&'a Path>,
+    timeout: Duration,
+    /// Budget for the doctest pass, timed separately from `timeout`: unlike
+    /// the compiled harness, `cargo test --doc` recompiles from scratch on
+    /// every invocation, so it can't share a baseline timed off the binary.
+    doctest_timeout: Duration,
+    loops_file: &'a Path,
+    with_doctests: bool,
+    extra_args: &'a [OsString],
+}
+
+fn run_mutations(
+    test_executable: &PathBuf,
+    with_coverage: bool,
+    list: &[String],
+    options: &RunOptions,
+) -> Result<()> {
+    let all_mutations = list
+        .iter()
+        .map(|m| Mutation::from(m))
+        .collect::<Result<Vec<_>>>()?;
+    // Captured before filtering so `check_baseline` can tell a mutation
+    // this run's `--filter`/`--exclude`/`--file` merely excluded apart from
+    // one that's genuinely gone from the codebase.
+    let all_keys: HashSet<String> = all_mutations.iter().map(mutation_key).collect();
+    let (mutations, skipped): (Vec<_>, Vec<_>) =
+        all_mutations.into_iter().partition(|m| options.filters.keeps(m));
+    let skipped = skipped.len();
+    let max_mutation = mutations.len();
+
+    if mutations.is_empty() {
+        match options.format {
+            OutputFormat::Json => print_json_report(&[], skipped, &HashSet::new()),
+            OutputFormat::Text | OutputFormat::Github => {
+                println!("No mutations matched the given filters ({} skipped)\n", skipped);
+            }
+        }
+        return Ok(());
+    }
+
+    if options.format != OutputFormat::Json {
+        println!(
+            "Running {} mutations across {} workers ({} skipped)\n",
+            max_mutation, options.jobs, skipped
+        );
+    }
+
+    // Mutations already known (from earlier in this run) to hang forever
+    // are reported as timeouts straight away, without spawning a child
+    // process for them again.
+    let known_loops = read_loop_counts(options.loops_file)?;
+    let (short_circuited, to_run): (Vec<_>, Vec<_>) = mutations
+        .iter()
+        .partition(|m| known_loops.contains(&m.count));
+
+    // Seed the work queue with every count still to run up front; workers just
+    // pull from it until it's drained.
+    let (work_tx, work_rx) = mpsc::channel::<usize>();
+    for mutation in &to_run {
+        work_tx.send(mutation.count).expect("work queue receiver dropped early");
+    }
+    drop(work_tx);
+    let work_rx = Arc::new(Mutex::new(work_rx));
+
+    let (result_tx, result_rx) = mpsc::channel::<(usize, Result<Status>)>();
+    let workers: Vec<_> = (0..options.jobs)
+        .map(|_| {
+            let work_rx = Arc::clone(&work_rx);
+            let result_tx = result_tx.clone();
+            let test_executable = test_executable.clone();
+            let timeout = options.timeout;
+            thread::spawn(move || {
+                let mut runner = new_runner(&test_executable, with_coverage);
+                runner.set_timeout(timeout);
+                loop {
+                    let count = match work_rx.lock().unwrap().recv() {
+                        Ok(count) => count,
+                        Err(_) => break,
+                    };
+                    let result = runner.run(count);
+                    if result_tx.send((count, result)).is_err() {
+                        break;
+                    }
+                }
+            })
+        })
+        .collect();
+    drop(result_tx);
+
+    let mut outcomes: Vec<(usize, Result<Status>)> = result_rx.into_iter().collect();
+    for worker in workers {
+        worker.join().expect("mutation worker thread panicked");
+    }
+    for mutation in &short_circuited {
+        outcomes.push((mutation.count, Ok(Status::Timeout)));
+    }
+    outcomes.sort_by_key(|&(count, _)| count);
+
+    let mut results: Vec<(&Mutation, Status)> = Vec::with_capacity(outcomes.len());
+    for (count, result) in outcomes {
+        let mutation = mutations
+            .iter()
+            .find(|m| m.count == count)
+            .expect("worker reported a count that was never queued");
+        let status = result?;
+        if let Status::Timeout = status {
+            if !known_loops.contains(&count) {
+                append_loop_count(options.loops_file, count)?;
+            }
+        }
+        results.push((mutation, status));
+    }
+
+    // Mutations that survived the compiled test harness get one more
+    // chance: maybe a doctest exercises the mutated code instead.
+    let mut caught_by_doctest = HashSet::new();
+    if options.with_doctests {
+        let mut doctest_runner = DoctestRunner::new(options.extra_args.to_vec());
+        doctest_runner.set_timeout(options.doctest_timeout);
+        for (mutation, status) in results.iter_mut() {
+            if *status == Status::Success {
+                let doctest_status = doctest_runner.run(mutation.count)?;
+                if doctest_status != Status::Success {
+                    *status = doctest_status;
+                    caught_by_doctest.insert(mutation.count);
+                }
+            }
+        }
+    }
+
+    // A succeeding test suite is actually a failure for us: at least one
+    // test should have failed. "killed" in the google paper, but let's
+    // avoid violent language -- bblum
+    let failures = results
+        .iter()
+        .filter(|(_, status)| *status == Status::Success)
+        .count();
+
+    match options.format {
+        OutputFormat::Text => print_text_report(&results, failures, max_mutation, skipped, &caught_by_doctest),
+        OutputFormat::Github => {
+            print_text_report(&results, failures, max_mutation, skipped, &caught_by_doctest);
+            print_github_annotations(&results);
+        }
+        OutputFormat::Json => print_json_report(&results, skipped, &caught_by_doctest),
+    }
+
+    if let Some(baseline) = options.baseline {
+        if check_baseline(baseline, &results, &all_keys)? {
+            bail!(
+                "mutation regression: a mutation survives that baseline {:?} doesn't list as surviving",
+                baseline
+            );
+        }
+    }
     Ok(())
 }
 
@@ -102,12 +552,33 @@ fn get_mutations_filename() -> Result<PathBuf> {
     Ok(mutagen_dir.join(MUTATIONS_LIST))
 }
 
-fn compile_tests() -> Result<Vec<PathBuf>> {
+/// The user's own trailing cargo args (`-p <pkg>`, `--features`,
+/// `--manifest-path`, ...), forwarded to every `cargo test` invocation we
+/// make so the doctest pass builds against the same target as the compiled
+/// harness.
+fn extra_cargo_args() -> Vec<OsString> {
+    // mutagen's own flags (and the values of those that take one) aren't cargo's
+    const OWN_FLAGS: &[&str] = &["--coverage", "--doctests"];
+    const OWN_VALUE_FLAGS: &[&str] =
+        &["--jobs", "--format", "--filter", "--exclude", "--file", "--baseline", "--timeout-factor"];
+    // We need to skip first two arguments (path to mutagen binary and "mutagen" string)
+    let mut args = std::env::args_os().skip(2);
+    let mut extra = Vec::new();
+    while let Some(arg) = args.next() {
+        if OWN_VALUE_FLAGS.iter().any(|f| arg == *f) {
+            args.next();
+        } else if !OWN_FLAGS.iter().any(|f| arg == *f) {
+            extra.push(arg);
+        }
+    }
+    extra
+}
+
+fn compile_tests(extra_args: &[OsString]) -> Result<Vec<PathBuf>> {
     let mut tests: Vec<PathBuf> = Vec::new();
     let compile_out = Command::new("cargo")
         .args(&["test", "--no-run", "--message-format=json"])
-        // We need to skip first two arguments (path to mutagen binary and "mutagen" string)
-        .args(std::env::args_os().skip(2))
+        .args(extra_args)
         .stderr(Stdio::inherit())
         .output()?;
 
@@ -141,14 +612,70 @@ fn read_mutations(filename: &PathBuf) -> Result<Vec<String>> {
         .collect())
 }
 
+/// Mutation counts already known, earlier in this same run, to hang the
+/// test suite; `loops_file` is wiped once per `cargo mutagen` invocation
+/// but accumulates across the test binaries run() iterates over.
+fn read_loop_counts(loops_file: &Path) -> Result<HashSet<usize>> {
+    if !loops_file.exists() {
+        return Ok(HashSet::new());
+    }
+    let mut file = File::open(loops_file)?;
+    let mut s = String::new();
+    file.read_to_string(&mut s)?;
+    s.lines()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.parse().map_err(failure::Error::from))
+        .collect()
+}
+
+fn append_loop_count(loops_file: &Path, count: usize) -> Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(loops_file)?;
+    writeln!(file, "{}", count)?;
+    Ok(())
+}
+
+/// Per-mutation wall-clock budget: `baseline` scaled by `--timeout-factor`
+/// (default `DEFAULT_TIMEOUT_FACTOR`), never below `TIMEOUT_FLOOR`.
+fn mutation_timeout(baseline: Duration) -> Result<Duration> {
+    let factor = match flag_value("--timeout-factor") {
+        Some(v) => v.parse()?,
+        None => DEFAULT_TIMEOUT_FACTOR,
+    };
+    Ok(std::cmp::max(baseline.mul_f64(factor), TIMEOUT_FLOOR))
+}
+
 fn has_flag(flag: &str) -> bool {
     let mut args = std::env::args_os();
 
     args.find(|f| f == flag).is_some()
 }
 
+/// Value passed after `flag` on the command line, e.g. `--jobs 4`.
+fn flag_value(flag: &str) -> Option<String> {
+    let mut args = std::env::args_os();
+    args.find(|f| f == flag)?;
+    args.next()?.into_string().ok()
+}
+
+/// Number of worker threads to run mutations with: `--jobs N` if given,
+/// otherwise one per available core.
+fn jobs() -> usize {
+    flag_value("--jobs")
+        .and_then(|v| v.parse().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        })
+}
+
 fn run() -> Result<()> {
-    let tests_executable = compile_tests()?;
+    let extra_args = extra_cargo_args();
+    let tests_executable = compile_tests(&extra_args)?;
     if tests_executable.is_empty() {
         bail!("executable path not found");
     }
@@ -156,18 +683,18 @@ fn run() -> Result<()> {
     let list = read_mutations(&filename)?;
 
     let with_coverage = has_flag("--coverage");
-    let (mut cov_runner, mut full_runner);
-    let _res = remove_file("target/mutagen/loops.txt");
+    let jobs = jobs();
+    let format = output_format()?;
+    let filters = Filters::from_args()?;
+    let baseline = flag_value("--baseline").map(PathBuf::from);
+    let with_doctests = has_flag("--doctests");
+    let loops_file = Path::new(LOOPS_FILE);
+    let _res = remove_file(loops_file);
     for test_executable in tests_executable {
         println!("test executable at {:?}", test_executable);
-        let runner: &mut Runner = if with_coverage {
-            cov_runner = CoverageRunner::new(test_executable.clone());
-            &mut cov_runner
-        } else {
-            full_runner = FullSuiteRunner::new(test_executable.clone());
-            &mut full_runner
-        };
+        let mut runner = new_runner(&test_executable, with_coverage);
 
+        let clean_run_started = Instant::now();
         if let Err(e) = runner.run(0) {
             bail!(
                 format!("Something horrible went wrong and I don't even know what: {:?}", e)
@@ -175,8 +702,33 @@ fn run() -> Result<()> {
                 //"You need to make sure you don't have failing tests before running 'cargo mutagen'"
             );
         }
+        let timeout = mutation_timeout(clean_run_started.elapsed())?;
+
+        // cargo test --doc recompiles the doctest binary from scratch, so its
+        // baseline has to be timed separately from the compiled harness
+        // above -- sharing `timeout` would budget compile time out of a
+        // near-instant unit-test run and time every doctest out.
+        let doctest_timeout = if with_doctests {
+            let mut doctest_runner = DoctestRunner::new(extra_args.clone());
+            let doctest_baseline_started = Instant::now();
+            doctest_runner.run(0)?;
+            mutation_timeout(doctest_baseline_started.elapsed())?
+        } else {
+            timeout
+        };
 
-        run_mutations(runner, &list)?
+        let options = RunOptions {
+            jobs,
+            format,
+            filters: &filters,
+            baseline: baseline.as_deref(),
+            timeout,
+            doctest_timeout,
+            loops_file,
+            with_doctests,
+            extra_args: &extra_args,
+        };
+        run_mutations(&test_executable, with_coverage, &list, &options)?
     }
     Ok(())
 }
@@ -190,7 +742,13 @@ fn main() {
 
 #[cfg(test)]
 mod tests {
-    use super::Mutation;
+    use std::collections::HashSet;
+    use std::time::Duration;
+
+    use super::{
+        append_loop_count, glob_to_regex, mutation_key, mutation_timeout, parse_span, read_loop_counts, Filters,
+        Mutation, TIMEOUT_FLOOR,
+    };
 
     #[test]
     fn it_decodes_well_formed_mutations() {
@@ -215,4 +773,71 @@ mod tests {
             assert!(mutation.is_err());
         }
     }
+
+    #[test]
+    fn it_parses_span_into_file_line_col() {
+        let span = parse_span("src/lib.rs:27:21: 27:22").unwrap();
+        assert_eq!(("src/lib.rs", "27", "21"), span);
+    }
+
+    #[test]
+    fn glob_matches_any_file_under_a_prefix() {
+        let re = glob_to_regex("src/foo/*").unwrap();
+        assert!(re.is_match("src/foo/bar.rs"));
+        assert!(!re.is_match("src/baz/bar.rs"));
+    }
+
+    #[test]
+    fn filters_combine_filter_exclude_and_file() {
+        let mutation = Mutation::from("2 - add one to int constant @ src/lib.rs:27:21: 27:22").unwrap();
+
+        let filters = Filters {
+            filter: Some(::regex::Regex::new("add one").unwrap()),
+            exclude: Some(::regex::Regex::new("subtract").unwrap()),
+            file: Some(glob_to_regex("src/*.rs").unwrap()),
+        };
+        assert!(filters.keeps(&mutation));
+
+        let filters = Filters {
+            filter: None,
+            exclude: Some(::regex::Regex::new("add one").unwrap()),
+            file: None,
+        };
+        assert!(!filters.keeps(&mutation));
+    }
+
+    #[test]
+    fn mutation_key_is_stable_across_counts() {
+        let a = Mutation::from("2 - add one to int constant @ src/lib.rs:27:21: 27:22").unwrap();
+        let b = Mutation::from("7 - add one to int constant @ src/lib.rs:27:21: 27:22").unwrap();
+        assert_eq!(mutation_key(&a), mutation_key(&b));
+    }
+
+    #[test]
+    fn mutation_timeout_floors_a_near_instant_baseline() {
+        let timeout = mutation_timeout(Duration::from_millis(1)).unwrap();
+        assert_eq!(TIMEOUT_FLOOR, timeout);
+    }
+
+    #[test]
+    fn mutation_timeout_scales_a_slower_baseline_by_the_default_factor() {
+        let timeout = mutation_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(Duration::from_secs(30), timeout);
+    }
+
+    #[test]
+    fn loop_counts_round_trip_through_the_file() {
+        let path = std::env::temp_dir().join("mutagen_test_loop_counts_round_trip.txt");
+        let _ = std::fs::remove_file(&path);
+
+        assert!(read_loop_counts(&path).unwrap().is_empty());
+
+        append_loop_count(&path, 3).unwrap();
+        append_loop_count(&path, 7).unwrap();
+
+        let counts: HashSet<usize> = [3, 7].iter().cloned().collect();
+        assert_eq!(counts, read_loop_counts(&path).unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,152 @@
+//! Drives the actual test binaries that mutagen's `main` hands mutation
+//! counts to, and reports back whether a given count survived.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
+use wait_timeout::ChildExt;
+
+type Result<T> = ::std::result::Result<T, ::failure::Error>;
+
+/// Default wall-clock budget given to a single run before we give up on it.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Outcome of running the test suite once, either against the clean
+/// baseline (count 0) or against a single mutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// the test suite passed -- for a mutation this means it survived
+    Success,
+    /// the test suite failed -- for a mutation this means it was caught
+    Failure,
+    /// the test suite didn't finish within its timeout -- also caught, but
+    /// worth reporting separately since it usually means the mutation
+    /// turned a loop into an infinite one
+    Timeout,
+}
+
+/// Something that can execute the test suite for a given mutation `count`
+/// (0 meaning "no mutation, run the clean baseline") and report whether it
+/// passed. Implementors spawn their own child process per call, so two
+/// `Runner`s never share state and can safely run on separate threads.
+pub trait Runner: Send {
+    fn run(&mut self, count: usize) -> Result<Status>;
+
+    /// Override the wall-clock budget given to each run. Runners that don't
+    /// spawn a child process can ignore this.
+    fn set_timeout(&mut self, _timeout: Duration) {}
+}
+
+/// Spawns `command` with the mutation `count` passed through the
+/// environment, the way the mutagen plugin's generated code expects, and
+/// turns its exit status into a `Status`.
+fn run_command(mut command: Command, count: usize, timeout: Duration) -> Result<Status> {
+    let mut child: Child = command
+        .env("MUTATION_COUNT", count.to_string())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    match child.wait_timeout(timeout)? {
+        Some(status) => Ok(if status.success() { Status::Success } else { Status::Failure }),
+        None => {
+            // The suite didn't finish in time; kill it so a hanging mutation
+            // doesn't wedge the runner.
+            child.kill()?;
+            child.wait()?;
+            Ok(Status::Timeout)
+        }
+    }
+}
+
+fn run_test_binary(test_executable: &PathBuf, count: usize, timeout: Duration) -> Result<Status> {
+    run_command(Command::new(test_executable), count, timeout)
+}
+
+/// Runs the whole test binary for every mutation, exactly as `cargo test` would.
+pub struct FullSuiteRunner {
+    test_executable: PathBuf,
+    timeout: Duration,
+}
+
+impl FullSuiteRunner {
+    pub fn new(test_executable: PathBuf) -> Self {
+        FullSuiteRunner {
+            test_executable,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl Runner for FullSuiteRunner {
+    fn run(&mut self, count: usize) -> Result<Status> {
+        run_test_binary(&self.test_executable, count, self.timeout)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+/// Like `FullSuiteRunner`, but meant to eventually narrow each run down to
+/// only the tests that coverage data says exercise the mutated span. For
+/// now it runs the same binary as `FullSuiteRunner`.
+pub struct CoverageRunner {
+    test_executable: PathBuf,
+    timeout: Duration,
+}
+
+impl CoverageRunner {
+    pub fn new(test_executable: PathBuf) -> Self {
+        CoverageRunner {
+            test_executable,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl Runner for CoverageRunner {
+    fn run(&mut self, count: usize) -> Result<Status> {
+        run_test_binary(&self.test_executable, count, self.timeout)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+/// Runs the crate's documentation examples via `cargo test --doc`, so
+/// mutations only ever exercised by a doctest still get caught. There's no
+/// separately compiled binary to reuse here -- rustdoc recompiles and runs
+/// the examples fresh on every invocation.
+pub struct DoctestRunner {
+    timeout: Duration,
+    /// The same trailing cargo args (`-p`, `--features`, `--manifest-path`,
+    /// ...) the user passed for the compiled test harness, so the doctest
+    /// pass builds against the same package/feature set.
+    extra_args: Vec<OsString>,
+}
+
+impl DoctestRunner {
+    pub fn new(extra_args: Vec<OsString>) -> Self {
+        DoctestRunner {
+            timeout: DEFAULT_TIMEOUT,
+            extra_args,
+        }
+    }
+}
+
+impl Runner for DoctestRunner {
+    fn run(&mut self, count: usize) -> Result<Status> {
+        let mut command = Command::new("cargo");
+        command.args(&["test", "--doc"]);
+        command.args(&self.extra_args);
+        run_command(command, count, self.timeout)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}